@@ -1,6 +1,8 @@
+use std::collections::{HashMap, HashSet};
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
-use pulldown_cmark::{Parser, Options, Tag, TagEnd, Event, HeadingLevel};
+use std::sync::{Mutex, OnceLock};
+use pulldown_cmark::{Parser, Options, Tag, TagEnd, Event, HeadingLevel, CodeBlockKind, Alignment};
 
 // 解析器状态结构体
 #[repr(C)]
@@ -14,6 +16,561 @@ pub struct MarkdownParser {
     code_color: *mut c_char,
     link_color: *mut c_char,
     text_color: *mut c_char,
+    autocorrect_enabled: bool,
+    wrap_width: u32,
+}
+
+// 读取存储的颜色字段，解析为 SGR 前景色片段（不含 "\x1b[" 和结尾 "m"）。
+// 支持两种格式：
+//   - `#rrggbb` 形式的十六进制字符串，渲染为 24 位真彩色 `38;2;R;G;B`
+//   - 其余情况按 256 色调色板索引处理，渲染为 `38;5;N`；不是 0-255 整数的值回退到 `38;5;255`
+fn color_to_sgr(color: &str) -> String {
+    if color.len() == 7 && color.is_ascii() && color.starts_with('#') {
+        let r = u8::from_str_radix(&color[1..3], 16);
+        let g = u8::from_str_radix(&color[3..5], 16);
+        let b = u8::from_str_radix(&color[5..7], 16);
+        if let (Ok(r), Ok(g), Ok(b)) = (r, g, b) {
+            return format!("38;2;{};{};{}", r, g, b);
+        }
+        // 以 # 开头但十六进制解析失败，说明不是合法的十六进制颜色，回退到默认白色，
+        // 避免把原始字符串拼进转义序列产生非法的 ANSI 输出
+        return "38;5;255".to_string();
+    }
+    if color.parse::<u8>().is_ok() {
+        format!("38;5;{}", color)
+    } else {
+        "38;5;255".to_string()
+    }
+}
+
+// 从 C 字符串字段读取颜色值，空指针时回退到默认值
+fn read_color(field: *mut c_char, default: &str) -> String {
+    if field.is_null() {
+        return default.to_string();
+    }
+    unsafe { CStr::from_ptr(field).to_string_lossy().into_owned() }
+}
+
+// 调用方通过 markdown_register_language_keywords 注册的额外关键字，
+// 按语言名分组，渲染时与内置关键字表合并查找
+static EXTRA_KEYWORDS: OnceLock<Mutex<HashMap<String, HashSet<String>>>> = OnceLock::new();
+
+fn extra_keywords_registry() -> &'static Mutex<HashMap<String, HashSet<String>>> {
+    EXTRA_KEYWORDS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// 注册额外的关键字，供 markdown_parse_to_ansi 高亮代码块时使用。
+// keywords_csv 是逗号分隔的关键字列表，可多次调用为同一语言追加
+#[no_mangle]
+pub extern "C" fn markdown_register_language_keywords(
+    language: *const c_char,
+    keywords_csv: *const c_char,
+) {
+    if language.is_null() || keywords_csv.is_null() {
+        return;
+    }
+
+    let lang = unsafe { CStr::from_ptr(language).to_string_lossy().into_owned() };
+    let csv = unsafe { CStr::from_ptr(keywords_csv).to_string_lossy().into_owned() };
+
+    let mut registry = extra_keywords_registry().lock().unwrap();
+    let entry = registry.entry(lang).or_default();
+    for kw in csv.split(',') {
+        let kw = kw.trim();
+        if !kw.is_empty() {
+            entry.insert(kw.to_string());
+        }
+    }
+}
+
+// 内置关键字表，按语言名（围栏代码块的 info string）索引，未知语言使用通用规则
+fn builtin_keywords(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "rust" | "rs" => &[
+            "fn", "let", "mut", "pub", "struct", "enum", "impl", "trait", "match", "if", "else",
+            "for", "while", "loop", "return", "use", "mod", "crate", "self", "Self", "super",
+            "as", "where", "async", "await", "move", "ref", "static", "const", "dyn", "unsafe",
+            "extern", "in", "break", "continue", "true", "false",
+        ],
+        "python" | "py" => &[
+            "def", "class", "import", "from", "as", "if", "elif", "else", "for", "while",
+            "return", "yield", "try", "except", "finally", "with", "lambda", "pass", "break",
+            "continue", "in", "is", "not", "and", "or", "None", "True", "False", "self",
+            "raise", "global", "nonlocal", "async", "await",
+        ],
+        "javascript" | "js" | "typescript" | "ts" => &[
+            "function", "const", "let", "var", "if", "else", "for", "while", "return", "class",
+            "extends", "import", "export", "from", "new", "this", "typeof", "instanceof", "try",
+            "catch", "finally", "throw", "async", "await", "true", "false", "null", "undefined",
+            "switch", "case", "break", "continue", "default", "in", "of",
+        ],
+        "go" => &[
+            "func", "package", "import", "var", "const", "if", "else", "for", "range", "return",
+            "struct", "interface", "type", "go", "chan", "select", "switch", "case", "default",
+            "break", "continue", "defer", "map", "true", "false", "nil",
+        ],
+        "c" | "cpp" | "c++" => &[
+            "int", "char", "float", "double", "void", "if", "else", "for", "while", "return",
+            "struct", "typedef", "static", "const", "switch", "case", "default", "break",
+            "continue", "sizeof", "unsigned", "signed", "long", "short", "class", "public",
+            "private", "protected", "namespace", "template", "new", "delete", "this", "true",
+            "false", "nullptr",
+        ],
+        "bash" | "sh" | "shell" => &[
+            "if", "then", "else", "elif", "fi", "for", "while", "do", "done", "function",
+            "return", "case", "esac", "in", "echo", "local", "export",
+        ],
+        _ => &["if", "else", "for", "while", "return", "function", "class", "import", "true", "false", "null"],
+    }
+}
+
+// 行注释前缀，按语言索引
+fn line_comment_prefixes(lang: &str) -> &'static [&'static str] {
+    match lang {
+        "rust" | "rs" | "go" | "c" | "cpp" | "c++" | "javascript" | "js" | "typescript" | "ts" => &["//"],
+        "python" | "py" | "bash" | "sh" | "shell" => &["#"],
+        _ => &["//", "#"],
+    }
+}
+
+// 块注释的起止分隔符，部分语言（如 python/bash）没有块注释
+fn block_comment_delims(lang: &str) -> Option<(&'static str, &'static str)> {
+    match lang {
+        "rust" | "rs" | "go" | "c" | "cpp" | "c++" | "javascript" | "js" | "typescript" | "ts" => {
+            Some(("/*", "*/"))
+        }
+        _ => None,
+    }
+}
+
+// 判断 chars[pos..] 是否以 pattern 开头
+fn chars_match_at(chars: &[char], pos: usize, pattern: &str) -> bool {
+    let plen = pattern.chars().count();
+    if pos + plen > chars.len() {
+        return false;
+    }
+    chars[pos..pos + plen].iter().collect::<String>() == pattern
+}
+
+// 从 start 开始查找 close 分隔符，返回其结束位置（未找到则到文本末尾）
+fn find_delim_end(chars: &[char], start: usize, close: &str) -> usize {
+    let clen = close.chars().count();
+    let mut i = start;
+    while i + clen <= chars.len() {
+        if chars[i..i + clen].iter().collect::<String>() == close {
+            return i + clen;
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+// 对围栏代码块内的文本做轻量级语法高亮：关键字、字符串、数字、注释分别上色，
+// 其余片段保持代码块的基础样式（base_style，即背景色+code_color）
+fn highlight_code(code: &str, lang: &str, base_style: &str) -> String {
+    let keywords: HashSet<&str> = builtin_keywords(lang).iter().copied().collect();
+    let registry = extra_keywords_registry().lock().unwrap();
+    let extra: Option<&HashSet<String>> = registry.get(lang);
+    let line_comments = line_comment_prefixes(lang);
+    let block_comment = block_comment_delims(lang);
+    let quotes: [char; 2] = ['"', '\''];
+
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::new();
+    let mut word = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        if let Some((open, close)) = block_comment {
+            if chars_match_at(&chars, i, open) {
+                flush_word(&mut out, &mut word, &keywords, extra, base_style);
+                let end = find_delim_end(&chars, i + open.chars().count(), close);
+                let segment: String = chars[i..end].iter().collect();
+                out.push_str("\x1b[3;38;5;242m");
+                out.push_str(&segment);
+                out.push_str("\x1b[0m");
+                out.push_str(base_style);
+                i = end;
+                continue;
+            }
+        }
+
+        if let Some(prefix) = line_comments.iter().find(|p| chars_match_at(&chars, i, p)) {
+            flush_word(&mut out, &mut word, &keywords, extra, base_style);
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map(|p| i + p)
+                .unwrap_or(chars.len());
+            let segment: String = chars[i..end].iter().collect();
+            out.push_str("\x1b[3;38;5;242m");
+            out.push_str(&segment);
+            out.push_str("\x1b[0m");
+            out.push_str(base_style);
+            i = end;
+            let _ = prefix;
+            continue;
+        }
+
+        if quotes.contains(&c) {
+            flush_word(&mut out, &mut word, &keywords, extra, base_style);
+            let quote = c;
+            let start = i;
+            i += 1;
+            while i < chars.len() && chars[i] != quote {
+                if chars[i] == '\\' && i + 1 < chars.len() {
+                    i += 1;
+                }
+                i += 1;
+            }
+            if i < chars.len() {
+                i += 1; // 消费闭合引号
+            }
+            let segment: String = chars[start..i].iter().collect();
+            out.push_str("\x1b[38;5;114m");
+            out.push_str(&segment);
+            out.push_str("\x1b[0m");
+            out.push_str(base_style);
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+            i += 1;
+            continue;
+        }
+
+        flush_word(&mut out, &mut word, &keywords, extra, base_style);
+        out.push(c);
+        i += 1;
+    }
+    flush_word(&mut out, &mut word, &keywords, extra, base_style);
+    out
+}
+
+// 将累积的单词按关键字/数字/普通标识符分类并上色后写入 out
+fn flush_word(
+    out: &mut String,
+    word: &mut String,
+    keywords: &HashSet<&str>,
+    extra: Option<&HashSet<String>>,
+    base_style: &str,
+) {
+    if word.is_empty() {
+        return;
+    }
+    let is_keyword = keywords.contains(word.as_str()) || extra.is_some_and(|e| e.contains(word.as_str()));
+    let is_number = word.chars().next().is_some_and(|c| c.is_ascii_digit());
+    if is_keyword {
+        out.push_str("\x1b[1;38;5;204m");
+        out.push_str(word);
+        out.push_str("\x1b[0m");
+        out.push_str(base_style);
+    } else if is_number {
+        out.push_str("\x1b[38;5;215m");
+        out.push_str(word);
+        out.push_str("\x1b[0m");
+        out.push_str(base_style);
+    } else {
+        out.push_str(word);
+    }
+    word.clear();
+}
+
+// 估算单个字符在终端中的显示宽度：CJK 表意文字、假名、谚文音节等东亚宽字符占 2 列，其余占 1 列
+fn char_display_width(c: char) -> usize {
+    let cp = c as u32;
+    let wide = matches!(cp,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x20000..=0x3FFFD
+    );
+    if wide { 2 } else { 1 }
+}
+
+// 计算字符串的终端显示宽度（而不是字节数或字符数），用于表格列对齐
+fn display_width(s: &str) -> usize {
+    s.chars().map(char_display_width).sum()
+}
+
+// 去掉 ANSI SGR 转义序列（`\x1b[...m`），用于计算带内联样式（加粗/链接等）的
+// 单元格文本的真实显示宽度——转义序列本身不占终端列宽
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for c in chars.by_ref() {
+                if c == 'm' {
+                    break;
+                }
+            }
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+// 按对齐方式将单元格文本填充到指定显示宽度
+fn pad_cell(text: &str, width: usize, align: Alignment) -> String {
+    let pad = width.saturating_sub(display_width(&strip_ansi(text)));
+    match align {
+        Alignment::Right => format!("{}{}", " ".repeat(pad), text),
+        Alignment::Center => {
+            let left = pad / 2;
+            let right = pad - left;
+            format!("{}{}{}", " ".repeat(left), text, " ".repeat(right))
+        }
+        Alignment::Left | Alignment::None => format!("{}{}", text, " ".repeat(pad)),
+    }
+}
+
+// 为脚注标签分配序号：首次出现（无论是引用还是定义）按出现顺序分配，之后复用同一序号
+fn assign_footnote_number(
+    label: &str,
+    numbers: &mut HashMap<String, usize>,
+    order: &mut Vec<String>,
+    next: &mut usize,
+) -> usize {
+    if let Some(&n) = numbers.get(label) {
+        return n;
+    }
+    let n = *next;
+    *next += 1;
+    numbers.insert(label.to_string(), n);
+    order.push(label.to_string());
+    n
+}
+
+// 私用区字符，用作哨兵包裹无匹配定义的脚注标签（见 mark_unmatched_footnote_refs）。
+// pulldown-cmark 只在存在匹配定义时才发出 Event::FootnoteReference，没有匹配定义的
+// `[^label]` 会作为普通文本原样流出，因此需要在喂给解析器之前先标记出来
+const FOOTNOTE_SENTINEL: char = '\u{FDD0}';
+
+enum TextSegment {
+    Plain(String),
+    UnmatchedFootnote(String),
+}
+
+// 按哨兵字符切分一个 Text 事件的内容，还原出普通文本片段和无匹配定义的脚注标签
+fn split_footnote_sentinels(text: &str) -> Vec<TextSegment> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(FOOTNOTE_SENTINEL) {
+        if start > 0 {
+            segments.push(TextSegment::Plain(rest[..start].to_string()));
+        }
+        let after_open = &rest[start + FOOTNOTE_SENTINEL.len_utf8()..];
+        match after_open.find(FOOTNOTE_SENTINEL) {
+            Some(end) => {
+                segments.push(TextSegment::UnmatchedFootnote(after_open[..end].to_string()));
+                rest = &after_open[end + FOOTNOTE_SENTINEL.len_utf8()..];
+            }
+            None => {
+                // 哨兵不成对，不是我们写入的标记，原样保留
+                segments.push(TextSegment::Plain(rest[start..].to_string()));
+                rest = "";
+            }
+        }
+    }
+    if !rest.is_empty() {
+        segments.push(TextSegment::Plain(rest.to_string()));
+    }
+    segments
+}
+
+// 扫描原始 Markdown，收集所有脚注定义 `[^label]:` 的标签（定义须位于行首，允许前导空白）
+fn scan_footnote_definitions(markdown: &str) -> HashSet<String> {
+    let mut defined = HashSet::new();
+    for line in markdown.lines() {
+        if let Some(rest) = line.trim_start().strip_prefix("[^") {
+            if let Some(close) = rest.find(']') {
+                if rest[close + 1..].starts_with(':') {
+                    defined.insert(rest[..close].to_string());
+                }
+            }
+        }
+    }
+    defined
+}
+
+// 把文档中找不到匹配定义的脚注引用 `[^label]` 替换为哨兵标记（定义本身原样保留，
+// 由解析器按正常的脚注定义处理）。事件循环遇到 Text 事件里的哨兵标记时，
+// 按 split_footnote_sentinels 还原出标签并分配编号、渲染引用
+fn mark_unmatched_footnote_refs(markdown: &str, defined: &HashSet<String>) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut i = 0;
+    while i < markdown.len() {
+        let rest = &markdown[i..];
+        if rest.starts_with("[^") {
+            if let Some(close) = rest.find(']') {
+                let label = &rest[2..close];
+                let after = &rest[close + 1..];
+                let label_valid = !label.is_empty() && !label.contains(char::is_whitespace);
+                if label_valid && !after.starts_with(':') && !defined.contains(label) {
+                    out.push(FOOTNOTE_SENTINEL);
+                    out.push_str(label);
+                    out.push(FOOTNOTE_SENTINEL);
+                    i += close + 1;
+                    continue;
+                }
+            }
+        }
+        let ch = rest.chars().next().unwrap();
+        out.push(ch);
+        i += ch.len_utf8();
+    }
+    out
+}
+
+// 将标题文本转换为 URL 安全的 slug：先整体转小写，再把连续空白折叠为单个连字符，
+// 最后丢弃既不是字母数字也不是 `_`/`-` 的字符
+fn slugify(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut collapsed = String::new();
+    let mut in_space = false;
+    for c in lower.chars() {
+        if c.is_whitespace() {
+            in_space = true;
+            continue;
+        }
+        if in_space {
+            collapsed.push('-');
+            in_space = false;
+        }
+        collapsed.push(c);
+    }
+    collapsed
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '_' || *c == '-')
+        .collect()
+}
+
+// 为重复的 slug 追加 -1、-2 等后缀，首次出现的 slug 保持不变
+fn dedupe_slug(base: &str, seen: &mut HashMap<String, usize>) -> String {
+    let count = seen.entry(base.to_string()).or_insert(0);
+    if *count == 0 {
+        *count += 1;
+        base.to_string()
+    } else {
+        let slug = format!("{}-{}", base, count);
+        *count += 1;
+        slug
+    }
+}
+
+// 判断字符是否属于 CJK 表意文字/假名/谚文音节（不含中日韩标点，避免标点两侧被加空格）
+fn is_cjk(c: char) -> bool {
+    let cp = c as u32;
+    matches!(cp,
+        0x3040..=0x30FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+    )
+}
+
+// 在 CJK 字符与半角字母/数字的边界处插入一个空格，使中英文混排更易读。
+// 只在两侧都不是已有空白时插入，因此对已规范化过的文本重复执行是幂等的
+fn cjk_autocorrect(text: &str) -> String {
+    let mut out = String::new();
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if let Some(p) = prev {
+            if !p.is_whitespace() && !c.is_whitespace() {
+                let boundary = (is_cjk(p) && c.is_ascii_alphanumeric())
+                    || (p.is_ascii_alphanumeric() && is_cjk(c));
+                if boundary {
+                    out.push(' ');
+                }
+            }
+        }
+        out.push(c);
+        prev = Some(c);
+    }
+    out
+}
+
+// 按显示宽度（而非字节/字符数）把文本换行追加到 result，在 wrap_width 为 0 时直接原样写入。
+// current_col 记录当前行已占用的显示列数，由调用方在段落开始、遇到换行等处维护；
+// 超出换行宽度时另起一行，写入 indent（对齐列表/引用的缩进）后重放 style_prefix（恢复当前激活的样式）
+fn wrap_push(
+    result: &mut String,
+    current_col: &mut usize,
+    text: &str,
+    wrap_width: usize,
+    indent: &str,
+    style_prefix: &str,
+) {
+    if wrap_width == 0 {
+        result.push_str(text);
+        *current_col += display_width(text);
+        return;
+    }
+
+    let indent_width = display_width(indent);
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                chars.next();
+            }
+            if *current_col > indent_width {
+                if *current_col + 1 > wrap_width {
+                    result.push('\n');
+                    result.push_str(indent);
+                    result.push_str(style_prefix);
+                    *current_col = indent_width;
+                } else {
+                    result.push(' ');
+                    *current_col += 1;
+                }
+            }
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            let word_width = display_width(&word);
+            if *current_col > indent_width && *current_col + word_width > wrap_width {
+                result.push('\n');
+                result.push_str(indent);
+                result.push_str(style_prefix);
+                *current_col = indent_width;
+            }
+            result.push_str(&word);
+            *current_col += word_width;
+        }
+    }
+}
+
+// 计算当前嵌套上下文（列表层级 + 引用边框 + 当前列表项标记宽度）下，
+// 软换行后续行应对齐的缩进字符串，使续行与内容对齐而不是顶格
+fn wrap_indent(list_stack: &[bool], blockquote_stack: &[String], extra_indent_width: usize) -> String {
+    let indent_level = list_stack.len().saturating_sub(1);
+    format!(
+        "{}{}{}",
+        "  ".repeat(indent_level),
+        blockquote_stack.concat(),
+        " ".repeat(extra_indent_width),
+    )
 }
 
 // 创建新的 Markdown 解析器
@@ -29,6 +586,8 @@ pub extern "C" fn markdown_parser_new() -> *mut MarkdownParser {
         code_color: CString::new("252").unwrap().into_raw(),
         link_color: CString::new("39").unwrap().into_raw(),
         text_color: CString::new("255").unwrap().into_raw(),
+        autocorrect_enabled: false,
+        wrap_width: 0,
     });
     Box::into_raw(parser)
 }
@@ -97,46 +656,114 @@ pub extern "C" fn markdown_parse_to_ansi(
     options.insert(Options::ENABLE_TABLES);
     options.insert(Options::ENABLE_TASKLISTS);
     options.insert(Options::ENABLE_FOOTNOTES);
-    
+
+    // pulldown-cmark 只在文档中存在匹配定义时才发出 Event::FootnoteReference，
+    // 没有匹配定义的 `[^label]` 引用需要提前标记，否则渲染时无法区分普通文本
+    let defined_footnote_labels = scan_footnote_definitions(markdown);
+    let markdown_marked = mark_unmatched_footnote_refs(markdown, &defined_footnote_labels);
+
     // 创建解析器
-    let parser_obj = Parser::new_ext(markdown, options);
-    
+    let parser_obj = Parser::new_ext(&markdown_marked, options);
+
+    // 读取可配置的颜色，转换为 SGR 片段
+    let heading_sgr = color_to_sgr(&read_color(parser_ref.heading_color, "86"));
+    let code_sgr = color_to_sgr(&read_color(parser_ref.code_color, "252"));
+    let link_sgr = color_to_sgr(&read_color(parser_ref.link_color, "39"));
+    let text_sgr = color_to_sgr(&read_color(parser_ref.text_color, "255"));
+    let base_style = format!("\x1b[{}m", text_sgr);
+
     // 渲染为 ANSI
     let mut result = String::new();
     let mut list_stack = Vec::new();
     let mut list_index_stack = Vec::new();
     let mut in_code_block = false;
-    
+    let mut code_lang = String::new();
+    let code_base_style = format!("\x1b[48;5;236m\x1b[{}m", code_sgr);
+
+    // 表格整体缓冲：流式渲染无法对齐列宽，所以先收集整张表再统一排版
+    let mut in_table = false;
+    let mut table_alignments: Vec<Alignment> = Vec::new();
+    let mut table_rows: Vec<Vec<String>> = Vec::new();
+    let mut current_row: Vec<String> = Vec::new();
+    let mut current_cell = String::new();
+
+    // 脚注：标签 -> 序号（按首次出现顺序分配），以及延迟渲染的定义正文
+    let mut footnote_numbers: HashMap<String, usize> = HashMap::new();
+    let mut footnote_order: Vec<String> = Vec::new();
+    let mut footnote_next: usize = 1;
+    let mut footnote_defs: HashMap<String, String> = HashMap::new();
+    let mut current_footnote_label = String::new();
+    let mut footnote_stash: Option<String> = None;
+
+    // 终端宽度感知的软换行：wrap_width 为 0 表示关闭
+    let wrap_width = parser_ref.wrap_width as usize;
+    let mut in_heading = false;
+    let mut current_col: usize = 0;
+    let mut blockquote_stack: Vec<String> = Vec::new();
+    let mut extra_indent_width: usize = 0;
+    let mut style_stack: Vec<String> = vec![base_style.clone()];
+
+    // 以配置的文本色作为基准色
+    result.push_str(&base_style);
+
     for event in parser_obj {
         match event {
             Event::Start(tag) => {
                 match tag {
                     Tag::Heading { level, .. } => {
+                        in_heading = true;
                         // 确保标题前有换行
                         if !result.is_empty() && !result.ends_with('\n') {
                             result.push('\n');
                         }
-                        result.push_str("\x1b[1;38;5;86m"); // 青色粗体
+                        result.push_str(&format!("\x1b[1;{}m", heading_sgr)); // 配置的标题色，粗体
+                        style_stack.push(format!(
+                            "\x1b[1;{}m{}",
+                            heading_sgr,
+                            if level == HeadingLevel::H1 { "\x1b[4m" } else { "" },
+                        ));
                         if level == HeadingLevel::H1 {
                             result.push_str("\x1b[4m"); // 一级标题加下划线
                         }
                     }
-                    Tag::CodeBlock(_) => {
+                    Tag::CodeBlock(kind) => {
                         in_code_block = true;
+                        code_lang = match kind {
+                            CodeBlockKind::Fenced(lang) => lang.to_string(),
+                            CodeBlockKind::Indented => String::new(),
+                        };
                         // 确保代码块前有换行
                         if !result.is_empty() && !result.ends_with('\n') {
                             result.push('\n');
                         }
-                        result.push_str("\x1b[48;5;236m\x1b[38;5;252m"); // 代码块样式
+                        result.push_str(&code_base_style); // 代码块样式
                     }
                     Tag::Emphasis => {
-                        result.push_str("\x1b[3;38;5;204m"); // 斜体粉色
+                        let style = "\x1b[3;38;5;204m"; // 斜体粉色
+                        if in_table {
+                            current_cell.push_str(style);
+                        } else {
+                            result.push_str(style);
+                        }
+                        style_stack.push(style.to_string());
                     }
                     Tag::Strong => {
-                        result.push_str("\x1b[1;38;5;203m"); // 粗体红色
+                        let style = "\x1b[1;38;5;203m"; // 粗体红色
+                        if in_table {
+                            current_cell.push_str(style);
+                        } else {
+                            result.push_str(style);
+                        }
+                        style_stack.push(style.to_string());
                     }
                     Tag::Link { .. } => {
-                        result.push_str("\x1b[4;38;5;39m"); // 蓝色下划线
+                        let style = format!("\x1b[4;{}m", link_sgr); // 配置的链接色，下划线
+                        if in_table {
+                            current_cell.push_str(&style);
+                        } else {
+                            result.push_str(&style);
+                        }
+                        style_stack.push(style);
                     }
                     Tag::List(ordered) => {
                         // 列表前确保有换行
@@ -152,24 +779,41 @@ pub extern "C" fn markdown_parse_to_ansi(
                         for _ in 0..indent_level {
                             result.push_str("  ");
                         }
+                        extra_indent_width = 0;
                         if let Some(&is_ordered) = list_stack.last() {
                             if is_ordered {
                                 let index = list_index_stack.last_mut().unwrap();
-                                result.push_str(&format!("\x1b[38;5;78m{}. \x1b[0m", index));
+                                let marker = format!("{}. ", index);
+                                extra_indent_width = display_width(&marker);
+                                result.push_str(&format!("\x1b[38;5;78m{}\x1b[0m", marker));
                                 *index += 1;
                             } else {
+                                extra_indent_width = display_width("• ");
                                 result.push_str("\x1b[38;5;78m• \x1b[0m");
                             }
                         }
+                        // 紧凑列表项的内容不会再经过 Tag::Paragraph，直接在这里记录起始列
+                        if wrap_width > 0 {
+                            let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                            current_col = display_width(&indent);
+                        }
                     }
                     Tag::BlockQuote(_) => {
                         if !result.is_empty() && !result.ends_with('\n') {
                             result.push('\n');
                         }
-                        result.push_str("\x1b[3;38;5;245m│ "); // 灰色斜体引用，添加边框
+                        let quote_style = "\x1b[3;38;5;245m";
+                        result.push_str(quote_style); // 灰色斜体引用
+                        result.push_str("│ "); // 添加边框
+                        blockquote_stack.push("│ ".to_string());
+                        style_stack.push(quote_style.to_string());
                     }
-                    Tag::Table(_) => {
-                        result.push_str("\x1b[38;5;240m"); // 表格样式
+                    Tag::Table(alignments) => {
+                        in_table = true;
+                        table_alignments = alignments;
+                        table_rows.clear();
+                        current_row.clear();
+                        current_cell.clear();
                     }
                     Tag::TableHead => {
                         // 表头
@@ -178,17 +822,51 @@ pub extern "C" fn markdown_parse_to_ansi(
                         // 表行
                     }
                     Tag::TableCell => {
-                        // 表格单元格
-                        result.push_str(" | ");
+                        current_cell.clear();
                     }
                     Tag::Strikethrough => {
-                        result.push_str("\x1b[9;38;5;240m"); // 删除线
+                        let style = "\x1b[9;38;5;240m"; // 删除线
+                        if in_table {
+                            current_cell.push_str(style);
+                        } else {
+                            result.push_str(style);
+                        }
+                        style_stack.push(style.to_string());
                     }
                     Tag::Paragraph => {
-                        // 段落开始 - 如果在列表中不添加额外换行
-                        if list_stack.is_empty() && !result.is_empty() && !result.ends_with('\n') {
-                            result.push('\n');
+                        // 段落开始 - 列表项内部不添加额外换行；引用块内紧跟边框的首段同理
+                        // （Tag::BlockQuote 刚写入的 "│ " 没有换行收尾，此时强行换行会把
+                        // 边框和内容拆成两行）。若引用块内确实需要换行，换行后要重新写入
+                        // 边框前缀，让续行保持对齐
+                        if list_stack.is_empty() && blockquote_stack.is_empty() {
+                            if !result.is_empty() && !result.ends_with('\n') {
+                                result.push('\n');
+                            }
+                        } else if list_stack.is_empty() {
+                            let quote_prefix = blockquote_stack.concat();
+                            if !result.is_empty() && !result.ends_with(quote_prefix.as_str()) {
+                                if !result.ends_with('\n') {
+                                    result.push('\n');
+                                }
+                                result.push_str(&quote_prefix);
+                            }
                         }
+                        if wrap_width > 0 {
+                            let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                            current_col = display_width(&indent);
+                        }
+                    }
+                    Tag::FootnoteDefinition(label) => {
+                        let label = label.to_string();
+                        assign_footnote_number(
+                            &label,
+                            &mut footnote_numbers,
+                            &mut footnote_order,
+                            &mut footnote_next,
+                        );
+                        current_footnote_label = label;
+                        // 定义正文不直接输出，暂存当前结果缓冲区，单独收集后挪到文末
+                        footnote_stash = Some(std::mem::take(&mut result));
                     }
                     _ => {}
                 }
@@ -196,15 +874,22 @@ pub extern "C" fn markdown_parse_to_ansi(
             Event::End(tag) => {
                 match tag {
                     TagEnd::Heading(_) => {
-                        result.push_str("\x1b[0m\n\n"); // 重置样式并添加换行
+                        in_heading = false;
+                        style_stack.pop();
+                        result.push_str("\x1b[0m");
+                        result.push_str(&base_style);
+                        result.push_str("\n\n"); // 重置样式并添加换行
                     }
                     TagEnd::CodeBlock => {
                         in_code_block = false;
+                        code_lang.clear();
                         // 确保代码块内容后有换行
                         if !result.ends_with('\n') {
                             result.push('\n');
                         }
-                        result.push_str("\x1b[0m\n"); // 重置样式并添加换行
+                        result.push_str("\x1b[0m");
+                        result.push_str(&base_style);
+                        result.push('\n'); // 重置样式并添加换行
                     }
                     TagEnd::Paragraph => {
                         // 段落结束 - 根据上下文添加换行
@@ -215,7 +900,14 @@ pub extern "C" fn markdown_parse_to_ansi(
                         }
                     }
                     TagEnd::Emphasis | TagEnd::Strong | TagEnd::Link => {
-                        result.push_str("\x1b[0m"); // 重置样式
+                        style_stack.pop();
+                        if in_table {
+                            current_cell.push_str("\x1b[0m");
+                            current_cell.push_str(&style_stack.concat()); // 恢复上一层激活的样式
+                        } else {
+                            result.push_str("\x1b[0m");
+                            result.push_str(&style_stack.concat()); // 恢复上一层激活的样式
+                        }
                     }
                     TagEnd::List(_) => {
                         list_stack.pop();
@@ -231,41 +923,193 @@ pub extern "C" fn markdown_parse_to_ansi(
                             result.push('\n');
                         }
                         result.push_str("\x1b[0m");
+                        extra_indent_width = 0;
                     }
                     TagEnd::BlockQuote(_) => {
-                        result.push_str("\x1b[0m\n\n");
+                        blockquote_stack.pop();
+                        style_stack.pop();
+                        result.push_str("\x1b[0m");
+                        result.push_str(&base_style);
+                        result.push_str("\n\n");
                     }
                     TagEnd::Table => {
-                        result.push_str("\x1b[0m\n\n");
+                        in_table = false;
+                        let col_count = table_alignments.len().max(
+                            table_rows.iter().map(|r| r.len()).max().unwrap_or(0),
+                        );
+                        let mut col_widths = vec![0usize; col_count];
+                        for row in &table_rows {
+                            for (i, cell) in row.iter().enumerate() {
+                                let w = display_width(&strip_ansi(cell.trim()));
+                                if w > col_widths[i] {
+                                    col_widths[i] = w;
+                                }
+                            }
+                        }
+
+                        if !result.is_empty() && !result.ends_with('\n') {
+                            result.push('\n');
+                        }
+                        result.push_str("\x1b[38;5;240m");
+                        for (row_idx, row) in table_rows.iter().enumerate() {
+                            result.push('│');
+                            for (col_idx, width) in col_widths.iter().enumerate() {
+                                let cell = row.get(col_idx).map(|s| s.trim()).unwrap_or("");
+                                let align = table_alignments
+                                    .get(col_idx)
+                                    .copied()
+                                    .unwrap_or(Alignment::None);
+                                result.push(' ');
+                                result.push_str(&pad_cell(cell, *width, align));
+                                result.push(' ');
+                                result.push('│');
+                            }
+                            result.push('\n');
+
+                            if row_idx == 0 {
+                                result.push('├');
+                                for (col_idx, width) in col_widths.iter().enumerate() {
+                                    result.push_str(&"─".repeat(width + 2));
+                                    result.push(if col_idx + 1 < col_widths.len() { '┼' } else { '┤' });
+                                }
+                                result.push('\n');
+                            }
+                        }
+                        result.push_str("\x1b[0m");
+                        result.push_str(&base_style);
+                        result.push('\n');
+
+                        table_rows.clear();
+                        table_alignments.clear();
                     }
-                    TagEnd::TableRow => {
-                        result.push_str(" |\n");
+                    TagEnd::TableHead | TagEnd::TableRow => {
+                        table_rows.push(std::mem::take(&mut current_row));
                     }
                     TagEnd::TableCell => {
-                        // 单元格结束
+                        current_row.push(std::mem::take(&mut current_cell));
                     }
                     TagEnd::Strikethrough => {
-                        result.push_str("\x1b[0m");
+                        style_stack.pop();
+                        if in_table {
+                            current_cell.push_str("\x1b[0m");
+                            current_cell.push_str(&style_stack.concat());
+                        } else {
+                            result.push_str("\x1b[0m");
+                            result.push_str(&style_stack.concat());
+                        }
+                    }
+                    TagEnd::FootnoteDefinition => {
+                        let body = std::mem::take(&mut result).trim().to_string();
+                        footnote_defs.insert(current_footnote_label.clone(), body);
+                        if let Some(prev) = footnote_stash.take() {
+                            result = prev;
+                        }
                     }
                     _ => {}
                 }
             }
             Event::Text(text) => {
-                result.push_str(&text);
+                if text.contains(FOOTNOTE_SENTINEL) {
+                    for segment in split_footnote_sentinels(&text) {
+                        match segment {
+                            TextSegment::Plain(s) => {
+                                if in_table {
+                                    if parser_ref.autocorrect_enabled {
+                                        current_cell.push_str(&cjk_autocorrect(&s));
+                                    } else {
+                                        current_cell.push_str(&s);
+                                    }
+                                } else if in_code_block {
+                                    result.push_str(&highlight_code(&s, &code_lang, &code_base_style));
+                                } else {
+                                    let processed = if parser_ref.autocorrect_enabled {
+                                        cjk_autocorrect(&s)
+                                    } else {
+                                        s.clone()
+                                    };
+                                    if !in_heading && wrap_width > 0 {
+                                        let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                                        let style_prefix = style_stack.concat();
+                                        wrap_push(&mut result, &mut current_col, &processed, wrap_width, &indent, &style_prefix);
+                                    } else {
+                                        result.push_str(&processed);
+                                    }
+                                }
+                            }
+                            // 没有匹配定义的脚注引用：仍按出现顺序分配编号，Footnotes 小节里
+                            // footnote_defs 查不到定义正文时会回退展示原始标签
+                            TextSegment::UnmatchedFootnote(label) => {
+                                let n = assign_footnote_number(
+                                    &label,
+                                    &mut footnote_numbers,
+                                    &mut footnote_order,
+                                    &mut footnote_next,
+                                );
+                                let marker = format!("\x1b[38;5;39m[{}]\x1b[0m", n);
+                                if in_table {
+                                    current_cell.push_str(&marker);
+                                } else {
+                                    result.push_str(&marker);
+                                }
+                            }
+                        }
+                    }
+                } else if in_table {
+                    if parser_ref.autocorrect_enabled {
+                        current_cell.push_str(&cjk_autocorrect(&text));
+                    } else {
+                        current_cell.push_str(&text);
+                    }
+                } else if in_code_block {
+                    result.push_str(&highlight_code(&text, &code_lang, &code_base_style));
+                } else {
+                    let processed = if parser_ref.autocorrect_enabled {
+                        cjk_autocorrect(&text)
+                    } else {
+                        text.to_string()
+                    };
+                    if !in_heading && wrap_width > 0 {
+                        let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                        let style_prefix = style_stack.concat();
+                        wrap_push(&mut result, &mut current_col, &processed, wrap_width, &indent, &style_prefix);
+                    } else {
+                        result.push_str(&processed);
+                    }
+                }
             }
             Event::Code(code) => {
-                result.push_str(&format!("\x1b[48;5;237m\x1b[38;5;220m{}\x1b[0m", code));
+                if in_table {
+                    current_cell.push_str(&code);
+                } else {
+                    result.push_str(&format!("\x1b[48;5;237m\x1b[38;5;220m{}\x1b[0m", code));
+                    result.push_str(&style_stack.concat());
+                    if !in_heading && wrap_width > 0 {
+                        current_col += display_width(&code);
+                    }
+                }
             }
             Event::SoftBreak => {
                 // 软换行 - 在代码块中保持为换行，普通文本中转为空格或换行
                 if in_code_block {
                     result.push('\n');
+                } else if !in_heading && wrap_width > 0 {
+                    let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                    let style_prefix = style_stack.concat();
+                    wrap_push(&mut result, &mut current_col, " ", wrap_width, &indent, &style_prefix);
                 } else {
                     result.push(' ');
                 }
             }
             Event::HardBreak => {
-                result.push('\n');
+                if !in_heading && wrap_width > 0 {
+                    let indent = wrap_indent(&list_stack, &blockquote_stack, extra_indent_width);
+                    result.push('\n');
+                    result.push_str(&indent);
+                    result.push_str(&style_stack.concat());
+                    current_col = display_width(&indent);
+                } else {
+                    result.push('\n');
+                }
             }
             Event::Rule => {
                 result.push_str("\n────────────────────\n\n");
@@ -276,9 +1120,27 @@ pub extern "C" fn markdown_parse_to_ansi(
                 } else {
                     result.push_str("\x1b[38;5;240m[ ]\x1b[0m ");
                 }
+                extra_indent_width += 4; // "[x] " 的显示宽度
+                // 任务列表标记写在 Tag::Item 记录 current_col 之后，需要同步补上
+                // 这段宽度，否则续行缩进与首行光标列不一致，导致换行错位
+                if wrap_width > 0 {
+                    current_col += 4;
+                }
             }
-            Event::FootnoteReference(_) => {
-                // 脚注引用
+            Event::FootnoteReference(label) => {
+                let label = label.to_string();
+                let n = assign_footnote_number(
+                    &label,
+                    &mut footnote_numbers,
+                    &mut footnote_order,
+                    &mut footnote_next,
+                );
+                let marker = format!("\x1b[38;5;39m[{}]\x1b[0m", n);
+                if in_table {
+                    current_cell.push_str(&marker);
+                } else {
+                    result.push_str(&marker);
+                }
             }
             Event::Html(html) => {
                 // HTML 内容
@@ -299,11 +1161,28 @@ pub extern "C" fn markdown_parse_to_ansi(
         }
     }
     
+    // 收集到脚注时，在正文之后追加分隔线和 Footnotes 小节；
+    // 未被引用的定义、以及没有对应定义的引用都需要出现在列表中
+    if !footnote_order.is_empty() {
+        if !result.is_empty() && !result.ends_with('\n') {
+            result.push('\n');
+        }
+        result.push_str("\n\x1b[38;5;240m────────────────────\x1b[0m\n");
+        result.push_str(&base_style);
+        result.push_str("Footnotes\n");
+        for label in &footnote_order {
+            let n = footnote_numbers[label];
+            let text = footnote_defs.get(label).cloned().unwrap_or_else(|| label.clone());
+            result.push_str(&format!("\x1b[38;5;39m[{}]\x1b[0m {}\n", n, text));
+        }
+    }
+
     // 清理多余的换行
     while result.ends_with("\n\n\n") {
         result.pop();
     }
-    
+    result.push_str("\x1b[0m"); // 结束时重置终端样式
+
     // 转换为 C 字符串
     match CString::new(result) {
         Ok(c_string) => c_string.into_raw(),
@@ -314,6 +1193,88 @@ pub extern "C" fn markdown_parse_to_ansi(
     }
 }
 
+// 提取文档大纲（目录）：解析 Markdown 并返回每个标题的级别、纯文本标题和 slug。
+// 结果按行序列化，每行为 `级别\tslug\t标题`，供 TUI 侧构建可跳转的大纲列表，
+// 无需重新实现一遍解析逻辑（与 rustdoc、mdbook 生成锚点 ID 的思路一致）
+#[no_mangle]
+pub extern "C" fn markdown_extract_toc(
+    parser: *mut MarkdownParser,
+    markdown_text: *const c_char,
+) -> *mut c_char {
+    if parser.is_null() || markdown_text.is_null() {
+        return std::ptr::null_mut();
+    }
+
+    let parser_ref = unsafe { &mut *parser };
+
+    // 清除之前的错误
+    if !parser_ref.error_message.is_null() {
+        let _ = unsafe { CString::from_raw(parser_ref.error_message) };
+        parser_ref.error_message = std::ptr::null_mut();
+    }
+
+    let markdown_cstr = unsafe { CStr::from_ptr(markdown_text) };
+    let markdown = match markdown_cstr.to_str() {
+        Ok(s) => s,
+        Err(_) => {
+            parser_ref.error_message = CString::new("Invalid UTF-8 input").unwrap().into_raw();
+            return std::ptr::null_mut();
+        }
+    };
+
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_FOOTNOTES);
+
+    let parser_obj = Parser::new_ext(markdown, options);
+
+    let mut headings: Vec<(HeadingLevel, String)> = Vec::new();
+    let mut in_heading = false;
+
+    for event in parser_obj {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                in_heading = true;
+                headings.push((level, String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                in_heading = false;
+            }
+            Event::Text(text) if in_heading => {
+                if let Some(last) = headings.last_mut() {
+                    last.1.push_str(&text);
+                }
+            }
+            Event::Code(code) if in_heading => {
+                if let Some(last) = headings.last_mut() {
+                    last.1.push_str(&code);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let lines: Vec<String> = headings
+        .into_iter()
+        .map(|(level, title)| {
+            let title = title.trim().to_string();
+            let slug = dedupe_slug(&slugify(&title), &mut seen);
+            format!("{}\t{}\t{}", level as u8, slug, title)
+        })
+        .collect();
+
+    match CString::new(lines.join("\n")) {
+        Ok(c_string) => c_string.into_raw(),
+        Err(_) => {
+            parser_ref.error_message = CString::new("Failed to create result string").unwrap().into_raw();
+            std::ptr::null_mut()
+        }
+    }
+}
+
 // 检查是否有错误发生
 #[no_mangle]
 pub extern "C" fn markdown_has_error(parser: *mut MarkdownParser) -> bool {
@@ -377,6 +1338,26 @@ pub extern "C" fn markdown_set_tasklist_enabled(parser: *mut MarkdownParser, ena
     }
 }
 
+// 设置 CJK/半角混排自动间距开关
+#[no_mangle]
+pub extern "C" fn markdown_set_autocorrect_enabled(parser: *mut MarkdownParser, enabled: bool) {
+    if !parser.is_null() {
+        unsafe {
+            (*parser).autocorrect_enabled = enabled;
+        }
+    }
+}
+
+// 设置软换行宽度（按终端列数），0 表示不换行
+#[no_mangle]
+pub extern "C" fn markdown_set_wrap_width(parser: *mut MarkdownParser, width: u32) {
+    if !parser.is_null() {
+        unsafe {
+            (*parser).wrap_width = width;
+        }
+    }
+}
+
 // 释放由 markdown_parse_to_ansi 返回的字符串
 #[no_mangle]
 pub extern "C" fn markdown_free_string(s: *mut c_char) {